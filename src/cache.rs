@@ -0,0 +1,158 @@
+//! Bounded LRU cache for indicator lookups, with negative caching.
+//!
+//! Real log streams repeat the same domains/IPs enormously, so caching both
+//! database hits (as the already-built enrichment `Value`) and misses (as a
+//! sentinel) avoids re-querying the database for indicators we've already
+//! resolved.
+
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Cached outcome of a database lookup for a single indicator.
+#[derive(Debug, Clone)]
+pub enum CacheEntry {
+    /// The indicator was found in the database; holds the already-built enrichment value.
+    Hit(Value),
+    /// The indicator was extracted but not found in the database.
+    Miss,
+}
+
+/// A node in the intrusive doubly-linked recency list.
+struct Node {
+    key: String,
+    entry: CacheEntry,
+    prev: Option<usize>,
+    next: Option<usize>,
+}
+
+/// Fixed-capacity LRU cache keyed by indicator string.
+///
+/// Backed by a `HashMap` for O(1) lookup plus an intrusive doubly-linked list
+/// (stored as a slab of slots) for O(1) recency tracking, so both reads and
+/// the eventual eviction are constant-time regardless of `capacity`.
+pub struct LookupCache {
+    capacity: usize,
+    slots: Vec<Option<Node>>,
+    index: HashMap<String, usize>,
+    free: Vec<usize>,
+    /// Most recently used slot.
+    head: Option<usize>,
+    /// Least recently used slot (eviction candidate).
+    tail: Option<usize>,
+}
+
+impl LookupCache {
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            slots: Vec::new(),
+            index: HashMap::new(),
+            free: Vec::new(),
+            head: None,
+            tail: None,
+        }
+    }
+
+    /// Look up `key`, marking it most-recently-used on a hit.
+    pub fn get(&mut self, key: &str) -> Option<&CacheEntry> {
+        let idx = *self.index.get(key)?;
+        self.move_to_front(idx);
+        Some(&self.slots[idx].as_ref().unwrap().entry)
+    }
+
+    /// Insert or update `key`, marking it most-recently-used. Evicts the
+    /// least-recently-used entry if this would exceed `capacity`.
+    pub fn put(&mut self, key: String, entry: CacheEntry) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        if let Some(&idx) = self.index.get(&key) {
+            self.slots[idx].as_mut().unwrap().entry = entry;
+            self.move_to_front(idx);
+            return;
+        }
+
+        if self.index.len() >= self.capacity {
+            self.evict_lru();
+        }
+
+        let idx = self.alloc_slot(Node {
+            key: key.clone(),
+            entry,
+            prev: None,
+            next: self.head,
+        });
+        if let Some(head) = self.head {
+            self.slots[head].as_mut().unwrap().prev = Some(idx);
+        }
+        self.head = Some(idx);
+        if self.tail.is_none() {
+            self.tail = Some(idx);
+        }
+        self.index.insert(key, idx);
+    }
+
+    /// Drop all entries. Called whenever the database is reloaded, since a
+    /// reload can change both positive and negative answers.
+    pub fn clear(&mut self) {
+        self.slots.clear();
+        self.index.clear();
+        self.free.clear();
+        self.head = None;
+        self.tail = None;
+    }
+
+    fn alloc_slot(&mut self, node: Node) -> usize {
+        if let Some(idx) = self.free.pop() {
+            self.slots[idx] = Some(node);
+            idx
+        } else {
+            self.slots.push(Some(node));
+            self.slots.len() - 1
+        }
+    }
+
+    fn move_to_front(&mut self, idx: usize) {
+        if self.head == Some(idx) {
+            return;
+        }
+        self.unlink(idx);
+        self.slots[idx].as_mut().unwrap().prev = None;
+        self.slots[idx].as_mut().unwrap().next = self.head;
+        if let Some(head) = self.head {
+            self.slots[head].as_mut().unwrap().prev = Some(idx);
+        }
+        self.head = Some(idx);
+        if self.tail.is_none() {
+            self.tail = Some(idx);
+        }
+    }
+
+    fn unlink(&mut self, idx: usize) {
+        let (prev, next) = {
+            let node = self.slots[idx].as_ref().unwrap();
+            (node.prev, node.next)
+        };
+        match prev {
+            Some(p) => self.slots[p].as_mut().unwrap().next = next,
+            None => self.head = next,
+        }
+        match next {
+            Some(n) => self.slots[n].as_mut().unwrap().prev = prev,
+            None => self.tail = prev,
+        }
+    }
+
+    fn evict_lru(&mut self) {
+        let Some(tail) = self.tail else { return };
+        self.unlink(tail);
+        let node = self.slots[tail].take().unwrap();
+        self.index.remove(&node.key);
+        self.free.push(tail);
+    }
+}
@@ -6,18 +6,26 @@
 //!
 //! ## Configuration
 //!
-//! Create `matchy.yaml` next to your `fluent-bit.yaml`:
+//! Create `matchy.yaml` next to your `fluent-bit.yaml` (optional - see below):
 //!
 //! ```yaml
 //! database: ./threats.mxy
 //!
-//! # Auto-reload: check for database updates every N seconds (0 = disabled, default)
+//! # Optional: only accept a reload whose bytes match this digest (also
+//! # checked against a ./threats.mxy.sha256 sidecar file if this is unset).
+//! # database_sha256: "3b8f...e21a"
+//!
+//! # Auto-reload: check for database AND matchy.yaml updates every N seconds (0 = disabled, default)
+//! # A bad edit to matchy.yaml is logged and ignored - the previous config keeps running.
 //! reload_interval_secs: 30
 //!
 //! # Output field names (optional)
 //! output_field: matchy_threats    # where match details go
 //! flag_field: threat_detected     # boolean flag added on match
 //!
+//! # Per-thread cache of indicator -> lookup result, with negative caching (default: 10000, 0 = disabled)
+//! lookup_cache_size: 10000
+//!
 //! # Extractor toggles (default: true)
 //! extract_domains: true
 //! extract_ipv4: true
@@ -29,6 +37,17 @@
 //! extract_monero: false
 //! ```
 //!
+//! ### Environment variable overrides
+//!
+//! Every key above can also be set (or overridden) as a `MATCHY_*`
+//! environment variable, e.g. `MATCHY_DATABASE`, `MATCHY_RELOAD_INTERVAL_SECS`,
+//! `MATCHY_OUTPUT_FIELD`, `MATCHY_EXTRACT_EMAILS`. Precedence is
+//! **env > matchy.yaml > built-in defaults**, and the file itself is
+//! optional - a container can run with just `MATCHY_DATABASE=/threats.mxy`
+//! and no `matchy.yaml` at all. At least `database` must resolve from one
+//! of those sources, or the plugin logs an error and passes records through
+//! unmodified.
+//!
 //! Fluent Bit config:
 //!
 //! ```yaml
@@ -41,6 +60,9 @@
 //!       accessible_paths: .
 //! ```
 
+mod cache;
+
+use cache::{CacheEntry, LookupCache};
 use matchy::{Database, QueryResult};
 use matchy_extractor::Extractor;
 use serde::Deserialize;
@@ -55,9 +77,17 @@ const CONFIG_FILE: &str = "matchy.yaml";
 /// Plugin configuration
 #[derive(Debug, Deserialize, Clone)]
 struct Config {
-    /// Path to the matchy database (.mxy file)
+    /// Path to the matchy database (.mxy file). Resolved from `matchy.yaml`
+    /// or `MATCHY_DATABASE`; at least one must be set.
+    #[serde(default)]
     database: String,
 
+    /// Expected SHA-256 checksum (hex) of `database`. If unset, falls back to
+    /// a `<database>.sha256` sidecar file if one exists. Either way, a reload
+    /// is only accepted when the digest of the bytes read matches.
+    #[serde(default)]
+    database_sha256: Option<String>,
+
     /// How often to check for database updates (seconds, 0 = disabled)
     #[serde(default = "default_reload_interval")]
     reload_interval_secs: u64,
@@ -70,6 +100,10 @@ struct Config {
     #[serde(default = "default_flag_field")]
     flag_field: String,
 
+    /// Max entries in the per-thread indicator lookup cache (0 = disabled)
+    #[serde(default = "default_lookup_cache_size")]
+    lookup_cache_size: usize,
+
     /// Extractor toggles
     #[serde(default = "default_true")]
     extract_domains: bool,
@@ -98,6 +132,9 @@ fn default_output_field() -> String {
 fn default_flag_field() -> String {
     "threat_detected".to_string()
 }
+fn default_lookup_cache_size() -> usize {
+    10_000
+}
 fn default_true() -> bool {
     true
 }
@@ -110,8 +147,12 @@ struct FilterState {
     initialized: bool,
     /// Last known mtime of the database file
     db_mtime: Option<SystemTime>,
+    /// Last known mtime of CONFIG_FILE
+    config_mtime: Option<SystemTime>,
     /// Unix timestamp (from record) when we last checked for updates
     last_reload_check_sec: u32,
+    /// Per-thread cache of indicator -> enrichment result (hit or miss)
+    lookup_cache: LookupCache,
 }
 
 impl FilterState {
@@ -122,7 +163,9 @@ impl FilterState {
             extractor: None,
             initialized: false,
             db_mtime: None,
+            config_mtime: None,
             last_reload_check_sec: 0,
+            lookup_cache: LookupCache::new(default_lookup_cache_size()),
         }
     }
 
@@ -132,25 +175,14 @@ impl FilterState {
         }
         self.initialized = true;
 
-        // Load configuration (YAML)
+        // Load configuration: matchy.yaml (optional) layered with MATCHY_* env vars.
+        // load_config() has already logged the reason on failure.
         let Some(config) = load_config() else {
-            eprintln!("[matchy] ERROR: No config found. Create {}.", CONFIG_FILE);
-            eprintln!("[matchy] Example {}:\ndatabase: ./threats.mxy", CONFIG_FILE);
             return;
         };
 
         // Initialize extractor from config
-        let extractor = match Extractor::builder()
-            .extract_domains(config.extract_domains)
-            .extract_emails(config.extract_emails)
-            .extract_ipv4(config.extract_ipv4)
-            .extract_ipv6(config.extract_ipv6)
-            .extract_hashes(config.extract_hashes)
-            .extract_bitcoin(config.extract_bitcoin)
-            .extract_ethereum(config.extract_ethereum)
-            .extract_monero(config.extract_monero)
-            .build()
-        {
+        let extractor = match build_extractor(&config) {
             Ok(e) => e,
             Err(e) => {
                 eprintln!("[matchy] ERROR: Failed to create extractor: {}", e);
@@ -158,48 +190,98 @@ impl FilterState {
             }
         };
 
+        self.lookup_cache = LookupCache::new(config.lookup_cache_size);
+
         // Load database
-        if self.load_database(&config.database) {
+        if self.load_database(&config) {
             if config.reload_interval_secs > 0 {
                 eprintln!(
                     "[matchy] Auto-reload enabled (checking every {}s)",
                     config.reload_interval_secs
                 );
             }
+            self.config_mtime = config_file_mtime();
             self.extractor = Some(extractor);
             self.config = Some(config);
         }
     }
 
-    /// Load database from path, updating mtime tracking. Returns true on success.
-    fn load_database(&mut self, path: &str) -> bool {
-        // Get current mtime
-        let mtime = std::fs::metadata(path).and_then(|m| m.modified()).ok();
-
-        match std::fs::read(path) {
-            Ok(bytes) => {
-                let size_mb = bytes.len() as f64 / (1024.0 * 1024.0);
-                match Database::from_bytes(bytes) {
-                    Ok(db) => {
-                        eprintln!("[matchy] Loaded {} ({:.1} MB)", path, size_mb);
-                        self.database = Some(db);
-                        self.db_mtime = mtime;
-                        true
-                    }
-                    Err(e) => {
-                        eprintln!("[matchy] ERROR: Failed to parse {}: {}", path, e);
-                        false
-                    }
-                }
+    /// Load the database at `config.database`, updating mtime tracking. Returns true on success.
+    ///
+    /// Guards against loading a file mid-rewrite by an external updater: the
+    /// file size and mtime must be stable across two consecutive stats taken
+    /// before and after the read, and if a checksum is configured (see
+    /// [`expected_checksum`]), the digest of the bytes read must match it.
+    /// On any failure, the currently loaded database is left untouched and
+    /// `db_mtime` is left unchanged, so once a database is loaded the next
+    /// reload interval retries. A failure of the first load in `initialize`
+    /// leaves no config in place, and `maybe_reload` does not retry it.
+    fn load_database(&mut self, config: &Config) -> bool {
+        let path = &config.database;
+
+        let stat = || std::fs::metadata(path).and_then(|m| Ok((m.len(), m.modified()?)));
+        let before = match stat() {
+            Ok(s) => s,
+            Err(e) => {
+                eprintln!("[matchy] ERROR: Cannot stat {}: {}", path, e);
+                return false;
             }
+        };
+
+        let bytes = match std::fs::read(path) {
+            Ok(bytes) => bytes,
             Err(e) => {
                 eprintln!("[matchy] ERROR: Cannot read {}: {}", path, e);
+                return false;
+            }
+        };
+
+        let after = match stat() {
+            Ok(s) => s,
+            Err(e) => {
+                eprintln!("[matchy] ERROR: Cannot stat {}: {}", path, e);
+                return false;
+            }
+        };
+
+        if before != after || before.0 != bytes.len() as u64 {
+            eprintln!(
+                "[matchy] WARNING: {} size/mtime changed while reading (likely mid-write), will retry",
+                path
+            );
+            return false;
+        }
+        let mtime = after.1;
+
+        if let Some(expected) = expected_checksum(config) {
+            let actual = sha256_hex(&bytes);
+            if !actual.eq_ignore_ascii_case(expected.trim()) {
+                eprintln!(
+                    "[matchy] WARNING: {} checksum mismatch (expected {}, got {}), keeping current database",
+                    path, expected.trim(), actual
+                );
+                return false;
+            }
+        }
+
+        let size_mb = bytes.len() as f64 / (1024.0 * 1024.0);
+        match Database::from_bytes(bytes) {
+            Ok(db) => {
+                eprintln!("[matchy] Loaded {} ({:.1} MB)", path, size_mb);
+                self.database = Some(db);
+                self.db_mtime = Some(mtime);
+                // A reload can change both positive and negative answers.
+                self.lookup_cache.clear();
+                true
+            }
+            Err(e) => {
+                eprintln!("[matchy] ERROR: Failed to parse {}: {}", path, e);
                 false
             }
         }
     }
 
-    /// Check if database file has been updated and reload if needed.
+    /// Check if `matchy.yaml` or the database file have been updated and reload if needed.
     /// Uses the record timestamp from Fluent Bit (zero syscall overhead).
     fn maybe_reload(&mut self, record_time_sec: u32) {
         let Some(config) = &self.config else { return };
@@ -217,7 +299,18 @@ impl FilterState {
         // Update last check time
         self.last_reload_check_sec = record_time_sec;
 
-        // Check file mtime (this is the only syscall, and only every N seconds)
+        // Check matchy.yaml first: a bad edit must never drop us into pass-through
+        // mode, so the previous config/extractor stay live on any failure.
+        if let Some(current_mtime) = config_file_mtime() {
+            if self.config_mtime != Some(current_mtime) {
+                self.reload_config(current_mtime);
+            }
+        }
+
+        // Re-borrow: reload_config may have swapped self.config.
+        let Some(config) = &self.config else { return };
+
+        // Check database file mtime (one stat besides the matchy.yaml one above, only every N seconds)
         let current_mtime = match std::fs::metadata(&config.database).and_then(|m| m.modified()) {
             Ok(mtime) => mtime,
             Err(_) => return, // Can't stat file, skip this check
@@ -230,18 +323,177 @@ impl FilterState {
 
         // File changed - reload!
         eprintln!("[matchy] Database file changed, reloading...");
-        let path = config.database.clone();
-        self.load_database(&path);
+        let config = config.clone();
+        self.load_database(&config);
     }
+
+    /// Re-parse `matchy.yaml` and rebuild the extractor, swapping both in atomically.
+    /// On any failure, the previously working config/extractor are left untouched
+    /// and the error is logged - a bad edit should never silently disable enrichment.
+    fn reload_config(&mut self, new_mtime: SystemTime) {
+        let Some(new_config) = load_config() else {
+            eprintln!(
+                "[matchy] ERROR: Failed to reload {}, keeping previous config",
+                CONFIG_FILE
+            );
+            return;
+        };
+
+        let new_extractor = match build_extractor(&new_config) {
+            Ok(e) => e,
+            Err(e) => {
+                eprintln!(
+                    "[matchy] ERROR: Failed to rebuild extractor from reloaded {}: {}, keeping previous config",
+                    CONFIG_FILE, e
+                );
+                return;
+            }
+        };
+
+        eprintln!("[matchy] {} changed, reloading config...", CONFIG_FILE);
+        if new_config.lookup_cache_size != self.lookup_cache.capacity() {
+            self.lookup_cache = LookupCache::new(new_config.lookup_cache_size);
+        }
+        self.config_mtime = Some(new_mtime);
+        self.extractor = Some(new_extractor);
+        self.config = Some(new_config);
+    }
+}
+
+/// Build an `Extractor` from the extractor toggles in `config`.
+fn build_extractor(config: &Config) -> Result<Extractor, String> {
+    Extractor::builder()
+        .extract_domains(config.extract_domains)
+        .extract_emails(config.extract_emails)
+        .extract_ipv4(config.extract_ipv4)
+        .extract_ipv6(config.extract_ipv6)
+        .extract_hashes(config.extract_hashes)
+        .extract_bitcoin(config.extract_bitcoin)
+        .extract_ethereum(config.extract_ethereum)
+        .extract_monero(config.extract_monero)
+        .build()
+        .map_err(|e| e.to_string())
+}
+
+/// Current mtime of `CONFIG_FILE`, if it exists and is stat-able.
+fn config_file_mtime() -> Option<SystemTime> {
+    std::fs::metadata(CONFIG_FILE).and_then(|m| m.modified()).ok()
+}
+
+/// Resolve the expected SHA-256 digest for `config.database`: an explicit
+/// `database_sha256`, falling back to a `<database>.sha256` sidecar file
+/// (the conventional `sha256sum`-style "<hex>  <filename>" format, or just
+/// the bare hex digest).
+fn expected_checksum(config: &Config) -> Option<String> {
+    if let Some(hash) = &config.database_sha256 {
+        return Some(hash.clone());
+    }
+    let sidecar = format!("{}.sha256", config.database);
+    let contents = std::fs::read_to_string(sidecar).ok()?;
+    contents.split_whitespace().next().map(str::to_string)
 }
 
-/// Load YAML config from matchy.yaml
+/// Hex-encoded SHA-256 digest of `bytes`.
+fn sha256_hex(bytes: &[u8]) -> String {
+    use sha2::{Digest, Sha256};
+    let digest = Sha256::digest(bytes);
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Load the plugin config: `matchy.yaml` (now optional) layered with
+/// `MATCHY_*` environment variable overrides, which take precedence over
+/// both the file and the built-in defaults. This lets operators run with
+/// zero config file - e.g. just `MATCHY_DATABASE=/threats.mxy` - and tune
+/// extractor toggles per-deployment without baking a file into the image.
 fn load_config() -> Option<Config> {
-    let contents = std::fs::read_to_string(CONFIG_FILE).ok()?;
-    match serde_yml::from_str(&contents) {
-        Ok(config) => Some(config),
+    // A missing file is fine (env vars may supply everything); a malformed
+    // one is not.
+    let contents = match std::fs::read_to_string(CONFIG_FILE) {
+        Ok(contents) => contents,
+        Err(_) => "{}".to_string(),
+    };
+    let mut config: Config = match serde_yml::from_str(&contents) {
+        Ok(config) => config,
         Err(e) => {
             eprintln!("[matchy] ERROR: Failed to parse {}: {}", CONFIG_FILE, e);
+            return None;
+        }
+    };
+
+    apply_env_overrides(&mut config);
+
+    if config.database.is_empty() {
+        eprintln!(
+            "[matchy] ERROR: No database configured. Set `database` in {} or MATCHY_DATABASE.",
+            CONFIG_FILE
+        );
+        return None;
+    }
+
+    Some(config)
+}
+
+/// Apply `MATCHY_*` environment variable overrides onto `config` in place.
+fn apply_env_overrides(config: &mut Config) {
+    if let Some(v) = env_var("MATCHY_DATABASE") {
+        config.database = v;
+    }
+    if let Some(v) = env_var("MATCHY_DATABASE_SHA256") {
+        config.database_sha256 = Some(v);
+    }
+    if let Some(v) = env_parse("MATCHY_RELOAD_INTERVAL_SECS") {
+        config.reload_interval_secs = v;
+    }
+    if let Some(v) = env_var("MATCHY_OUTPUT_FIELD") {
+        config.output_field = v;
+    }
+    if let Some(v) = env_var("MATCHY_FLAG_FIELD") {
+        config.flag_field = v;
+    }
+    if let Some(v) = env_parse("MATCHY_LOOKUP_CACHE_SIZE") {
+        config.lookup_cache_size = v;
+    }
+    if let Some(v) = env_parse("MATCHY_EXTRACT_DOMAINS") {
+        config.extract_domains = v;
+    }
+    if let Some(v) = env_parse("MATCHY_EXTRACT_EMAILS") {
+        config.extract_emails = v;
+    }
+    if let Some(v) = env_parse("MATCHY_EXTRACT_IPV4") {
+        config.extract_ipv4 = v;
+    }
+    if let Some(v) = env_parse("MATCHY_EXTRACT_IPV6") {
+        config.extract_ipv6 = v;
+    }
+    if let Some(v) = env_parse("MATCHY_EXTRACT_HASHES") {
+        config.extract_hashes = v;
+    }
+    if let Some(v) = env_parse("MATCHY_EXTRACT_BITCOIN") {
+        config.extract_bitcoin = v;
+    }
+    if let Some(v) = env_parse("MATCHY_EXTRACT_ETHEREUM") {
+        config.extract_ethereum = v;
+    }
+    if let Some(v) = env_parse("MATCHY_EXTRACT_MONERO") {
+        config.extract_monero = v;
+    }
+}
+
+/// Read an env var, treating an empty value the same as unset.
+fn env_var(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|v| !v.is_empty())
+}
+
+/// Read and parse an env var, logging a warning and ignoring it if set but malformed.
+fn env_parse<T: std::str::FromStr>(name: &str) -> Option<T>
+where
+    T::Err: std::fmt::Display,
+{
+    let raw = env_var(name)?;
+    match raw.parse() {
+        Ok(v) => Some(v),
+        Err(e) => {
+            eprintln!("[matchy] WARNING: Ignoring invalid {}={:?}: {}", name, raw, e);
             None
         }
     }
@@ -290,6 +542,9 @@ pub extern "C" fn matchy_filter(
         let mut state = state.borrow_mut();
         state.initialize();
         state.maybe_reload(time_sec);
+        // Reborrow as a plain reference so the fields below (db, lookup_cache, ...)
+        // can be borrowed disjointly instead of all going through `RefMut`'s `Deref`.
+        let state: &mut FilterState = &mut state;
 
         let (db, config, extractor) = match (&state.database, &state.config, &state.extractor) {
             (Some(db), Some(cfg), Some(ext)) => (db, cfg, ext),
@@ -304,29 +559,47 @@ pub extern "C" fn matchy_filter(
             return pass_through(record_slice);
         }
 
-        // Query database for each extracted IoC
+        // Query database for each extracted IoC, going through the per-thread
+        // lookup cache first (real streams repeat the same indicators a lot).
         let mut matches: Vec<Value> = Vec::new();
         for m in extracted {
             let indicator = m.item.as_value();
-            if let Ok(Some(result)) = db.lookup(&indicator) {
-                let data = match &result {
-                    QueryResult::Ip { data, prefix_len } => json!({
-                        "data": format!("{:?}", data),
-                        "prefix_len": prefix_len,
-                    }),
-                    QueryResult::Pattern { pattern_ids, data } => json!({
-                        "pattern_ids": pattern_ids,
-                        "data": data.iter().map(|d| format!("{:?}", d)).collect::<Vec<_>>(),
-                    }),
-                    QueryResult::NotFound => continue,
+
+            let data = if let Some(entry) = state.lookup_cache.get(&indicator) {
+                match entry {
+                    CacheEntry::Hit(data) => Some(data.clone()),
+                    CacheEntry::Miss => None,
+                }
+            } else {
+                let data = match db.lookup(&indicator) {
+                    Ok(Some(result)) => match &result {
+                        QueryResult::Ip { data, prefix_len } => Some(json!({
+                            "data": format!("{:?}", data),
+                            "prefix_len": prefix_len,
+                        })),
+                        QueryResult::Pattern { pattern_ids, data } => Some(json!({
+                            "pattern_ids": pattern_ids,
+                            "data": data.iter().map(|d| format!("{:?}", d)).collect::<Vec<_>>(),
+                        })),
+                        QueryResult::NotFound => None,
+                    },
+                    Ok(None) | Err(_) => None,
                 };
-                matches.push(json!({
-                    "indicator": indicator,
-                    "type": m.item.type_name(),
-                    "span": [m.span.0, m.span.1],
-                    "result": data,
-                }));
-            }
+                let cache_entry = match &data {
+                    Some(data) => CacheEntry::Hit(data.clone()),
+                    None => CacheEntry::Miss,
+                };
+                state.lookup_cache.put(indicator.clone(), cache_entry);
+                data
+            };
+
+            let Some(data) = data else { continue };
+            matches.push(json!({
+                "indicator": indicator,
+                "type": m.item.type_name(),
+                "span": [m.span.0, m.span.1],
+                "result": data,
+            }));
         }
 
         if matches.is_empty() {